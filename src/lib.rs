@@ -6,48 +6,142 @@ use deno_core::plugin_api::ZeroCopyBuf;
 use futures::future::FutureExt;
 use futures::future::poll_fn;
 
+use percent_encoding::utf8_percent_encode;
+use percent_encoding::NON_ALPHANUMERIC;
+
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use serde::Serialize;
 
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::collections::VecDeque;
 use std::ffi::CStr;
 use std::ffi::CString;
+use std::fmt::Display;
 use std::os::raw::*;
-use std::ptr::null_mut;
+use std::task::Poll;
 
 use webview_sys::*;
 
 thread_local! {
     static INSTANCE_INDEX: RefCell<u32> = RefCell::new(0);
     static INSTANCE_MAP: RefCell<HashMap<u32, *mut CWebView>> = RefCell::new(HashMap::new());
+    static INVOKE_QUEUES: RefCell<HashMap<u32, VecDeque<String>>> = RefCell::new(HashMap::new());
 }
 
 #[no_mangle]
 pub fn deno_plugin_init(interface: &mut dyn Interface) {
     interface.register_op("webview_new", op_webview_new);
-    // interface.register_op("webview_free", op_webview_free);
+    interface.register_op("webview_free", op_webview_free);
     interface.register_op("webview_exit", op_webview_exit);
     interface.register_op("webview_eval", op_webview_eval);
-    // interface.register_op("webview_dispatch", op_webview_dispatch);
+    interface.register_op("webview_dispatch", op_webview_dispatch);
     interface.register_op("webview_set_color", op_webview_set_color);
     interface.register_op("webview_set_title", op_webview_set_title);
+    interface.register_op("webview_dialog", op_webview_dialog);
     interface.register_op("webview_set_fullscreen", op_webview_set_fullscreen);
     interface.register_op("webview_loop", op_webview_loop);
     interface.register_op("webview_get_user_data", op_webview_get_user_data);
+    interface.register_op("webview_poll_events", op_webview_poll_events);
+}
+
+/// A named error class mirroring Deno's own error classes (e.g.
+/// `InvalidData`, `NotFound`, `TypeError`), so JS can `catch` and branch on
+/// `err.class` instead of the whole plugin panicking on bad input.
+#[derive(Serialize)]
+struct WebViewError {
+    class: &'static str,
+    message: String,
 }
 
+impl WebViewError {
+    fn new(class: &'static str, message: impl Display) -> Self {
+        WebViewError {
+            class,
+            message: message.to_string(),
+        }
+    }
+
+    fn not_found(id: u32) -> Self {
+        WebViewError::new("NotFound", format!("Could not find instance of id {}", id))
+    }
+
+    fn invalid_data(message: impl Display) -> Self {
+        WebViewError::new("InvalidData", message)
+    }
+
+    fn type_error(message: impl Display) -> Self {
+        WebViewError::new("TypeError", message)
+    }
+}
 
 #[derive(Serialize)]
 struct WebViewResponse<T> {
-    err: Option<String>,
+    err: Option<WebViewError>,
     ok: Option<T>,
 }
 
+fn response_buf<T: Serialize>(result: Result<T, WebViewError>) -> Buf {
+    let response = match result {
+        Ok(ok) => WebViewResponse {
+            err: None,
+            ok: Some(ok),
+        },
+        Err(err) => WebViewResponse {
+            err: Some(err),
+            ok: None,
+        },
+    };
+
+    serde_json::to_vec(&response).unwrap().into_boxed_slice()
+}
+
+fn respond<T: Serialize>(result: Result<T, WebViewError>) -> Op {
+    Op::Sync(response_buf(result))
+}
+
+fn parse_params<T: DeserializeOwned>(data: &[u8]) -> Result<T, WebViewError> {
+    serde_json::from_slice(data).map_err(WebViewError::invalid_data)
+}
+
+fn cstring(s: String) -> Result<CString, WebViewError> {
+    CString::new(s).map_err(WebViewError::type_error)
+}
+
+fn instance(id: u32) -> Result<*mut CWebView, WebViewError> {
+    INSTANCE_MAP.with(|cell| {
+        cell.borrow()
+            .get(&id)
+            .copied()
+            .ok_or_else(|| WebViewError::not_found(id))
+    })
+}
+
+/// How long a pending poll waits before checking again. Polling ops
+/// (`webview_loop`, `webview_poll_events`) have nothing external to hang a
+/// real waker off, so they re-arm it from a one-shot timer thread instead of
+/// calling `wake_by_ref()` immediately, which would busy-spin the op thread.
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(16);
+
+fn wake_later(waker: std::task::Waker) {
+    std::thread::spawn(move || {
+        std::thread::sleep(POLL_INTERVAL);
+        waker.wake();
+    });
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Content {
+    Url(String),
+    Html(String),
+}
+
 #[derive(Deserialize)]
 struct WebViewNewParams {
     title: String,
-    url: String,
+    content: Content,
     width: i32,
     height: i32,
     resizable: bool,
@@ -65,54 +159,113 @@ fn op_webview_new(
     data: &[u8],
     _zero_copy: Option<ZeroCopyBuf>,
 ) -> Op {
-    let mut response: WebViewResponse<WebViewNewResult> = WebViewResponse {
-        err: None,
-        ok: None,
+    let params: WebViewNewParams = match parse_params(data) {
+        Ok(params) => params,
+        Err(err) => return respond::<WebViewNewResult>(Err(err)),
     };
 
-    let params: WebViewNewParams = serde_json::from_slice(data).unwrap();
-
     let mut instance_id: u32 = 0;
     INSTANCE_INDEX.with(|cell| {
         instance_id = cell.replace_with(|&mut i| i + 1);
     });
 
+    let result = (|| -> Result<WebViewNewResult, WebViewError> {
+        let title = cstring(params.title)?;
+        let url = cstring(match params.content {
+            Content::Url(url) => url,
+            Content::Html(html) => format!(
+                "data:text/html,{}",
+                utf8_percent_encode(&html, NON_ALPHANUMERIC)
+            ),
+        })?;
+
+        // The invoke callback only receives the `CWebView` pointer and the
+        // message, so stash our instance id in the native user-data slot and
+        // read it back in `ffi_invoke_handler` (the box is reclaimed in
+        // `op_webview_free`).
+        let user_data = Box::into_raw(Box::new(instance_id)) as *mut c_void;
+
+        unsafe {
+            INSTANCE_MAP.with(|cell| {
+                cell.borrow_mut().insert(
+                    instance_id,
+                    webview_new(
+                        title.as_ptr(),
+                        url.as_ptr(),
+                        params.width,
+                        params.height,
+                        params.resizable as i32,
+                        params.debug as i32,
+                        params.frameless as i32,
+                        Some(ffi_invoke_handler),
+                        user_data,
+                    ),
+                );
+            });
+        }
+
+        Ok(WebViewNewResult { id: instance_id })
+    })();
+
+    respond(result)
+}
+
+extern "C" fn ffi_invoke_handler(webview: *mut CWebView, arg: *const c_char) {
     unsafe {
-        INSTANCE_MAP.with(|cell| {
-            let title = CString::new(params.title).unwrap();
-            let url = CString::new(params.url).unwrap();
-
-            cell.borrow_mut().insert(
-                instance_id,
-                webview_new(
-                    title.as_ptr(),
-                    url.as_ptr(),
-                    params.width,
-                    params.height,
-                    params.resizable as i32,
-                    params.debug as i32,
-                    params.frameless as i32,
-                    None, // Some(ffi_invoke_handler),
-                    null_mut(),
-                ),
-            );
+        let message = CStr::from_ptr(arg).to_string_lossy().into_owned();
+        let instance_id = *(webview_get_user_data(webview) as *const u32);
+
+        INVOKE_QUEUES.with(|cell| {
+            cell.borrow_mut()
+                .entry(instance_id)
+                .or_insert_with(VecDeque::new)
+                .push_back(message);
         });
     }
+}
 
-    response.ok = Some(WebViewNewResult { id: instance_id });
+#[derive(Deserialize)]
+struct WebViewFreeParams {
+    id: u32,
+}
 
-    let result: Buf = serde_json::to_vec(&response).unwrap().into_boxed_slice();
+#[derive(Serialize)]
+struct WebViewFreeResult {}
 
-    Op::Sync(result)
-}
+fn op_webview_free(
+    _interface: &mut dyn Interface,
+    data: &[u8],
+    _zero_copy: Option<ZeroCopyBuf>,
+) -> Op {
+    let params: WebViewFreeParams = match parse_params(data) {
+        Ok(params) => params,
+        Err(err) => return respond::<WebViewFreeResult>(Err(err)),
+    };
+
+    let result = INSTANCE_MAP.with(|cell| {
+        cell.borrow_mut()
+            .remove(&params.id)
+            .ok_or_else(|| WebViewError::not_found(params.id))
+    });
+
+    let result = result.map(|instance| {
+        unsafe {
+            // Reclaim the instance id we boxed into the native user-data
+            // slot back in `op_webview_new` before the pointer dies.
+            drop(Box::from_raw(webview_get_user_data(instance) as *mut u32));
+
+            webview_free(instance);
+        }
+
+        INVOKE_QUEUES.with(|cell| {
+            cell.borrow_mut().remove(&params.id);
+        });
+
+        WebViewFreeResult {}
+    });
 
-// extern "C" fn ffi_invoke_handler(webview: *mut CWebView, arg: *const c_char) {
-//     unsafe {
-//         let arg = CStr::from_ptr(arg).to_string_lossy().to_string();
-// 
-//         println!("{}", arg);
-//     }
-// }
+    respond(result)
+}
 
 #[derive(Deserialize)]
 struct WebViewExitParams {
@@ -127,30 +280,20 @@ fn op_webview_exit(
     data: &[u8],
     _zero_copy: Option<ZeroCopyBuf>,
 ) -> Op {
-    unsafe {
-        let mut response: WebViewResponse<WebViewExitResult> = WebViewResponse {
-            err: None,
-            ok: None,
-        };
-
-        let params: WebViewExitParams = serde_json::from_slice(data).unwrap();
-
-        INSTANCE_MAP.with(|cell| {
-            let instance_map = cell.borrow_mut();
-
-            if !instance_map.contains_key(&params.id) {
-                response.err = Some(format!("Could not find instance of id {}", &params.id))
-            } else {
-                let instance: *mut CWebView = *instance_map.get(&params.id).unwrap();
+    let params: WebViewExitParams = match parse_params(data) {
+        Ok(params) => params,
+        Err(err) => return respond::<WebViewExitResult>(Err(err)),
+    };
 
-                webview_exit(instance);
+    let result = instance(params.id).map(|instance| {
+        unsafe {
+            webview_exit(instance);
+        }
 
-                response.ok = Some(WebViewExitResult {});
-            }
-        });
+        WebViewExitResult {}
+    });
 
-        Op::Sync(serde_json::to_vec(&response).unwrap().into_boxed_slice())
-    }
+    respond(result)
 }
 
 #[derive(Deserialize)]
@@ -167,36 +310,67 @@ fn op_webview_eval(
     data: &[u8],
     _zero_copy: Option<ZeroCopyBuf>,
 ) -> Op {
-    unsafe {
-        let mut response: WebViewResponse<WebViewEvalResult> = WebViewResponse {
-            err: None,
-            ok: None,
-        };
+    let params: WebViewEvalParams = match parse_params(data) {
+        Ok(params) => params,
+        Err(err) => return respond::<WebViewEvalResult>(Err(err)),
+    };
 
-        let params: WebViewEvalParams = serde_json::from_slice(data).unwrap();
+    let result = instance(params.id).and_then(|instance| {
+        let js = cstring(params.js)?;
 
-        INSTANCE_MAP.with(|cell| {
-            let instance_map = cell.borrow_mut();
+        match unsafe { webview_eval(instance, js.as_ptr()) } {
+            0 => Ok(WebViewEvalResult {}),
+            _ => Err(WebViewError::new("Error", "Could not evaluate javascript")),
+        }
+    });
 
-            if !instance_map.contains_key(&params.id) {
-                response.err = Some(format!("Could not find instance of id {}", &params.id))
-            } else {
-                let instance: *mut CWebView = *instance_map.get(&params.id).unwrap();
-                let js = CString::new(params.js).unwrap();
+    respond(result)
+}
 
-                match webview_eval(instance, js.as_ptr()) {
-                    0 => {
-                        response.ok = Some(WebViewEvalResult {});
-                    }
-                    _ => response.err = Some("Could not evaluate javascript".to_string()),
-                }
-            }
-        });
+#[derive(Deserialize)]
+struct WebViewDispatchParams {
+    id: u32,
+    js: String,
+}
 
-        Op::Sync(serde_json::to_vec(&response).unwrap().into_boxed_slice())
+#[derive(Serialize)]
+struct WebViewDispatchResult {}
+
+extern "C" fn ffi_dispatch_handler(webview: *mut CWebView, arg: *mut c_void) {
+    unsafe {
+        let js = Box::from_raw(arg as *mut CString);
+
+        webview_eval(webview, js.as_ptr());
     }
 }
 
+// Schedules `js` to run on the UI thread during the next `webview_loop`
+// iteration via the native dispatch mechanism, so background tasks (e.g. a
+// Deno worker) can push results into the UI without racing `webview_eval`.
+fn op_webview_dispatch(
+    _interface: &mut dyn Interface,
+    data: &[u8],
+    _zero_copy: Option<ZeroCopyBuf>,
+) -> Op {
+    let params: WebViewDispatchParams = match parse_params(data) {
+        Ok(params) => params,
+        Err(err) => return respond::<WebViewDispatchResult>(Err(err)),
+    };
+
+    let result = instance(params.id).and_then(|instance| {
+        let js = cstring(params.js)?;
+        let arg = Box::into_raw(Box::new(js)) as *mut c_void;
+
+        unsafe {
+            webview_dispatch(instance, Some(ffi_dispatch_handler), arg);
+        }
+
+        Ok(WebViewDispatchResult {})
+    });
+
+    respond(result)
+}
+
 #[derive(Deserialize)]
 struct WebViewSetColorParams {
     id: u32,
@@ -214,30 +388,20 @@ fn op_webview_set_color(
     data: &[u8],
     _zero_copy: Option<ZeroCopyBuf>,
 ) -> Op {
-    unsafe {
-        let mut response: WebViewResponse<WebViewSetColorResult> = WebViewResponse {
-            err: None,
-            ok: None,
-        };
-
-        let params: WebViewSetColorParams = serde_json::from_slice(data).unwrap();
-
-        INSTANCE_MAP.with(|cell| {
-            let instance_map = cell.borrow_mut();
-
-            if !instance_map.contains_key(&params.id) {
-                response.err = Some(format!("Could not find instance of id {}", &params.id))
-            } else {
-                let instance: *mut CWebView = *instance_map.get(&params.id).unwrap();
+    let params: WebViewSetColorParams = match parse_params(data) {
+        Ok(params) => params,
+        Err(err) => return respond::<WebViewSetColorResult>(Err(err)),
+    };
 
-                webview_set_color(instance, params.r, params.g, params.b, params.a);
+    let result = instance(params.id).map(|instance| {
+        unsafe {
+            webview_set_color(instance, params.r, params.g, params.b, params.a);
+        }
 
-                response.ok = Some(WebViewSetColorResult {});
-            }
-        });
+        WebViewSetColorResult {}
+    });
 
-        Op::Sync(serde_json::to_vec(&response).unwrap().into_boxed_slice())
-    }
+    respond(result)
 }
 
 #[derive(Deserialize)]
@@ -254,31 +418,77 @@ fn op_webview_set_title(
     data: &[u8],
     _zero_copy: Option<ZeroCopyBuf>,
 ) -> Op {
-    unsafe {
-        let mut response: WebViewResponse<WebViewSetTitleResult> = WebViewResponse {
-            err: None,
-            ok: None,
-        };
+    let params: WebViewSetTitleParams = match parse_params(data) {
+        Ok(params) => params,
+        Err(err) => return respond::<WebViewSetTitleResult>(Err(err)),
+    };
 
-        let params: WebViewSetTitleParams = serde_json::from_slice(data).unwrap();
+    let result = instance(params.id).and_then(|instance| {
+        let title = cstring(params.title)?;
 
-        INSTANCE_MAP.with(|cell| {
-            let instance_map = cell.borrow_mut();
+        unsafe {
+            webview_set_title(instance, title.as_ptr());
+        }
 
-            if !instance_map.contains_key(&params.id) {
-                response.err = Some(format!("Could not find instance of id {}", &params.id))
-            } else {
-                let instance: *mut CWebView = *instance_map.get(&params.id).unwrap();
-                let title = CString::new(params.title).unwrap();
+        Ok(WebViewSetTitleResult {})
+    });
 
-                webview_set_title(instance, title.as_ptr());
+    respond(result)
+}
 
-                response.ok = Some(WebViewSetTitleResult {});
-            }
-        });
+#[derive(Deserialize)]
+struct WebViewDialogParams {
+    id: u32,
+    dialog_type: i32,
+    flags: i32,
+    title: String,
+    arg: String,
+}
 
-        Op::Sync(serde_json::to_vec(&response).unwrap().into_boxed_slice())
-    }
+#[derive(Serialize)]
+struct WebViewDialogResult {
+    value: String,
+}
+
+// Native dialogs (message boxes, open/save file pickers) must run on the UI
+// thread, so this op has to be invoked between `webview_loop` iterations
+// rather than from a background task.
+fn op_webview_dialog(
+    _interface: &mut dyn Interface,
+    data: &[u8],
+    _zero_copy: Option<ZeroCopyBuf>,
+) -> Op {
+    let params: WebViewDialogParams = match parse_params(data) {
+        Ok(params) => params,
+        Err(err) => return respond::<WebViewDialogResult>(Err(err)),
+    };
+
+    let result = instance(params.id).and_then(|instance| {
+        let title = cstring(params.title)?;
+        let arg = cstring(params.arg)?;
+
+        let mut result_buf = [0 as c_char; 4096];
+
+        unsafe {
+            webview_dialog(
+                instance,
+                params.dialog_type,
+                params.flags,
+                title.as_ptr(),
+                arg.as_ptr(),
+                result_buf.as_mut_ptr(),
+                result_buf.len(),
+            );
+
+            let value = CStr::from_ptr(result_buf.as_ptr())
+                .to_string_lossy()
+                .into_owned();
+
+            Ok(WebViewDialogResult { value })
+        }
+    });
+
+    respond(result)
 }
 
 #[derive(Deserialize)]
@@ -295,36 +505,25 @@ fn op_webview_set_fullscreen(
     data: &[u8],
     _zero_copy: Option<ZeroCopyBuf>,
 ) -> Op {
-    unsafe {
-        let mut response: WebViewResponse<WebViewSetFullscreenResult> = WebViewResponse {
-            err: None,
-            ok: None,
-        };
-
-        let params: WebViewSetFullscreenParams = serde_json::from_slice(data).unwrap();
-
-        INSTANCE_MAP.with(|cell| {
-            let instance_map = cell.borrow_mut();
-
-            if !instance_map.contains_key(&params.id) {
-                response.err = Some(format!("Could not find instance of id {}", &params.id))
-            } else {
-                let instance: *mut CWebView = *instance_map.get(&params.id).unwrap();
+    let params: WebViewSetFullscreenParams = match parse_params(data) {
+        Ok(params) => params,
+        Err(err) => return respond::<WebViewSetFullscreenResult>(Err(err)),
+    };
 
-                webview_set_fullscreen(instance, params.fullscreen as i32);
+    let result = instance(params.id).map(|instance| {
+        unsafe {
+            webview_set_fullscreen(instance, params.fullscreen as i32);
+        }
 
-                response.ok = Some(WebViewSetFullscreenResult {});
-            }
-        });
+        WebViewSetFullscreenResult {}
+    });
 
-        Op::Sync(serde_json::to_vec(&response).unwrap().into_boxed_slice())
-    }
+    respond(result)
 }
 
 #[derive(Deserialize)]
 struct WebViewLoopParams {
     id: u32,
-    blocking: i32,
 }
 
 #[derive(Serialize)]
@@ -332,35 +531,36 @@ struct WebViewLoopResult {
     code: i32,
 }
 
+// Pumps `webview_loop` in non-blocking mode on every poll instead of running
+// it synchronously on the op thread, so `await webview.run()` on the JS side
+// replaces a hand-written busy-loop of repeated `webview_loop` calls. Each
+// pending poll re-arms via `wake_later` rather than waking itself
+// immediately, so the op thread actually idles between iterations instead
+// of spinning.
 fn op_webview_loop(
     _interface: &mut dyn Interface,
     data: &[u8],
     _zero_copy: Option<ZeroCopyBuf>,
 ) -> Op {
-    unsafe {
-        let mut response: WebViewResponse<WebViewLoopResult> = WebViewResponse {
-            err: None,
-            ok: None,
-        };
-
-        let params: WebViewLoopParams = serde_json::from_slice(data).unwrap();
-
-        INSTANCE_MAP.with(|cell| {
-            let instance_map = cell.borrow_mut();
-
-            if !instance_map.contains_key(&params.id) {
-                response.err = Some(format!("Could not find instance of id {}", &params.id))
-            } else {
-                let instance: *mut CWebView = *instance_map.get(&params.id).unwrap();
+    let params: WebViewLoopParams = match parse_params(data) {
+        Ok(params) => params,
+        Err(err) => return Op::Sync(response_buf::<WebViewLoopResult>(Err(err))),
+    };
 
-                response.ok = Some(WebViewLoopResult {
-                    code: webview_loop(instance, params.blocking),
-                });
+    let fut = poll_fn(move |cx| match instance(params.id) {
+        Err(err) => Poll::Ready(Err(err)),
+        Ok(instance) => match unsafe { webview_loop(instance, 0) } {
+            0 => {
+                wake_later(cx.waker().clone());
+                Poll::Pending
             }
-        });
+            code => Poll::Ready(Ok(WebViewLoopResult { code })),
+        },
+    });
 
-        Op::Sync(serde_json::to_vec(&response).unwrap().into_boxed_slice())
-    }
+    let fut = fut.then(|result| async move { response_buf(result) });
+
+    Op::Async(fut.boxed())
 }
 
 #[derive(Deserialize)]
@@ -376,29 +576,66 @@ fn op_webview_get_user_data(
     data: &[u8],
     _zero_copy: Option<ZeroCopyBuf>,
 ) -> Op {
-    unsafe {
-        let mut response: WebViewResponse<WebViewGetUserDataResult> = WebViewResponse {
-            err: None,
-            ok: None,
-        };
+    let params: WebViewGetUserDataParams = match parse_params(data) {
+        Ok(params) => params,
+        Err(err) => return Op::Sync(response_buf::<WebViewGetUserDataResult>(Err(err))),
+    };
 
-        let params: WebViewGetUserDataParams = serde_json::from_slice(data).unwrap();
+    let fut = async move {
+        let result = instance(params.id).map(|_instance| WebViewGetUserDataResult {});
 
-        let fut = async move {
-            INSTANCE_MAP.with(|cell| {
-                let instance_map = cell.borrow_mut();
-
-                if !instance_map.contains_key(&params.id) {
-                    response.err = Some(format!("Could not find instance of id {}", &params.id))
-                } else {
-                    let instance: *mut CWebView = *instance_map.get(&params.id).unwrap();
-                    response.ok = Some(WebViewGetUserDataResult {})
-                }
-            });
+        response_buf(result)
+    };
 
-            serde_json::to_vec(&response).unwrap().into_boxed_slice()
-        };
+    Op::Async(fut.boxed())
+}
 
-        Op::Async(fut.boxed())
-    }
+#[derive(Deserialize)]
+struct WebViewPollEventsParams {
+    id: u32,
+}
+
+#[derive(Serialize)]
+struct WebViewPollEventsResult {
+    messages: Vec<String>,
+}
+
+// Resolves with the next batch of `window.external.invoke(...)` messages
+// queued for this instance, so TypeScript can simply `await` them instead of
+// polling the op thread itself.
+fn op_webview_poll_events(
+    _interface: &mut dyn Interface,
+    data: &[u8],
+    _zero_copy: Option<ZeroCopyBuf>,
+) -> Op {
+    let params: WebViewPollEventsParams = match parse_params(data) {
+        Ok(params) => params,
+        Err(err) => return Op::Sync(response_buf::<WebViewPollEventsResult>(Err(err))),
+    };
+
+    let fut = poll_fn(move |cx| {
+        if let Err(err) = instance(params.id) {
+            return Poll::Ready(Err(err));
+        }
+
+        let messages = INVOKE_QUEUES.with(|cell| {
+            cell.borrow_mut()
+                .get_mut(&params.id)
+                .map(|queue| queue.drain(..).collect::<Vec<String>>())
+                .unwrap_or_default()
+        });
+
+        if messages.is_empty() {
+            wake_later(cx.waker().clone());
+            Poll::Pending
+        } else {
+            Poll::Ready(Ok(messages))
+        }
+    });
+
+    let fut = fut.then(|result: Result<Vec<String>, WebViewError>| async move {
+        response_buf(result.map(|messages| WebViewPollEventsResult { messages }))
+    });
+
+    Op::Async(fut.boxed())
 }